@@ -1,12 +1,80 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, Address, Env, Symbol
+    contract, contracterror, contractclient, contractimpl, contracttype, Address, Env, Symbol, Vec
 };
 use soroban_sdk::token; // standard token interface (SAC-compatible)
 
 #[contracttype]
 #[derive(Clone, Copy, PartialEq)]
-pub enum EscrowStatus { Init, Funded, Released, Refunded }
+pub enum EscrowStatus { Init, Funded, Released, Refunded, Settled }
+
+/// Mirrors `UtilitiesOracle::Reading` structurally so this contract can
+/// decode the cross-contract call's return value without depending on that
+/// crate directly (this repo has no shared workspace to pull it from).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reading {
+    pub kwh: i64,
+    pub gas: i64,
+    pub water: i64,
+}
+
+/// The subset of `UtilitiesOracle`'s interface this contract calls into.
+#[contractclient(name = "UtilitiesOracleClient")]
+pub trait UtilitiesOracleInterface {
+    fn get_reading(env: Env, unit: Symbol, period: Symbol) -> Reading;
+}
+
+/// A single installment within a milestone escrow, e.g. one month's rent.
+/// `released`/`refunded` are mutually exclusive terminal flags on the
+/// milestone itself, mirroring `EscrowStatus`'s terminal states for the
+/// escrow as a whole.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Milestone {
+    pub amount: i128,
+    pub released: bool,
+    pub refunded: bool,
+}
+
+/// Structured failure codes, returned instead of panicking, so a caller can
+/// distinguish a wrong-state escrow from an unauthorized caller without
+/// parsing a trap message.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    AlreadyInit = 1,
+    NotInit = 2,
+    BadState = 3,
+    Unauthorized = 4,
+    MilestoneNotFound = 5,
+    AlreadyResolved = 6,
+    OverRelease = 7,
+    Disputed = 8,
+    NotTimedOut = 9,
+    NotConfigured = 10,
+    WrongMode = 11,
+}
+
+/// Roles an address can hold against this escrow, checked via `has_role`
+/// rather than a single hardcoded address, so arbitrators can be rotated or
+/// multiplied without redeploying.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Role {
+    Admin,
+    Arbitrator,
+}
+
+/// Composite storage key for a single `(role, address)` grant, mirroring the
+/// `ReadingKey` keying pattern used by the utilities oracle.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleKey {
+    pub role: Role,
+    pub addr: Address,
+}
 
 #[contract]
 pub struct Escrow;
@@ -16,34 +84,123 @@ impl Escrow {
     // instance storage keys
     fn k_tenant()  -> Symbol { Symbol::short("ten") }
     fn k_landlord()-> Symbol { Symbol::short("ll") }
-    fn k_arbit()   -> Symbol { Symbol::short("arb") }
     fn k_token()   -> Symbol { Symbol::short("tok") }
     fn k_amount()  -> Symbol { Symbol::short("amt") }
     fn k_status()  -> Symbol { Symbol::short("st")  }
+    fn k_admin()   -> Symbol { Symbol::short("admin") }
+    fn k_milestones() -> Symbol { Symbol::short("ms") }
+    fn k_released_total() -> Symbol { Symbol::short("reltot") }
+    fn k_refunded_total() -> Symbol { Symbol::short("reftot") }
+    fn k_release_deadline() -> Symbol { Symbol::short("reldl") }
+    fn k_refund_deadline()  -> Symbol { Symbol::short("refdl") }
+    fn k_disputed() -> Symbol { Symbol::short("disp") }
+    fn k_util_oracle()    -> Symbol { Symbol::short("uoracle") }
+    fn k_util_unit()      -> Symbol { Symbol::short("uunit") }
+    fn k_util_period()    -> Symbol { Symbol::short("uperiod") }
+    fn k_util_allowance() -> Symbol { Symbol::short("uallow") }
+    fn k_util_prices()    -> Symbol { Symbol::short("uprices") }
+
+    // Central topic symbols so indexers have one place to learn what to subscribe to.
+    fn topic_escrow()   -> Symbol { Symbol::short("escrow") }
+    fn topic_funded()   -> Symbol { Symbol::short("funded") }
+    fn topic_released() -> Symbol { Symbol::short("released") }
+    fn topic_refunded() -> Symbol { Symbol::short("refunded") }
+    fn topic_settled()  -> Symbol { Symbol::short("settled") }
+    fn topic_milestone() -> Symbol { Symbol::short("milestone") }
 
-    /// One-time initializer.
-    pub fn init(e: Env, tenant: Address, landlord: Address, arbitrator: Address,
-                token: Address, amount: i128) {
+    /// One-time initializer. Seeds `admin` (who alone can grant/revoke roles)
+    /// and grants the initial `arbitrator` the `Arbitrator` role so existing
+    /// callers don't need a separate `grant_role` call to get started.
+    /// `release_deadline`/`refund_deadline` are optional ledger timestamps
+    /// `claim_timeout` can act on if the arbitrator never shows up; leave
+    /// either `None` to require an explicit `release`/`refund` instead.
+    pub fn init(e: Env, admin: Address, tenant: Address, landlord: Address,
+                arbitrator: Address, token: Address, amount: i128,
+                release_deadline: Option<u64>, refund_deadline: Option<u64>) -> Result<(), Error> {
         // Any caller can deploy; identities are recorded here.
         if e.storage().instance().has(&Self::k_status()) {
-            panic!("already inited");
+            return Err(Error::AlreadyInit);
         }
+        e.storage().instance().set(&Self::k_admin(),    &admin);
         e.storage().instance().set(&Self::k_tenant(),   &tenant);
         e.storage().instance().set(&Self::k_landlord(), &landlord);
-        e.storage().instance().set(&Self::k_arbit(),    &arbitrator);
         e.storage().instance().set(&Self::k_token(),    &token);
         e.storage().instance().set(&Self::k_amount(),   &amount);
         e.storage().instance().set(&Self::k_status(),   &EscrowStatus::Init);
+        if let Some(d) = release_deadline { e.storage().instance().set(&Self::k_release_deadline(), &d); }
+        if let Some(d) = refund_deadline { e.storage().instance().set(&Self::k_refund_deadline(), &d); }
+
+        Self::grant_role_internal(&e, Role::Arbitrator, arbitrator);
+        Ok(())
+    }
+
+    /// One-time initializer for a milestone (e.g. monthly rent) escrow:
+    /// `amounts` becomes a vector of unresolved `Milestone`s summing to the
+    /// total `deposit` will transfer in. `release_milestone`/`refund_milestone`
+    /// then disburse period-by-period instead of all at once.
+    pub fn init_milestones(e: Env, admin: Address, tenant: Address, landlord: Address,
+                            arbitrator: Address, token: Address, amounts: Vec<i128>) -> Result<(), Error> {
+        if e.storage().instance().has(&Self::k_status()) {
+            return Err(Error::AlreadyInit);
+        }
+        let mut total: i128 = 0;
+        let mut milestones = Vec::new(&e);
+        for amount in amounts.iter() {
+            total += amount;
+            milestones.push_back(Milestone { amount, released: false, refunded: false });
+        }
+
+        e.storage().instance().set(&Self::k_admin(),    &admin);
+        e.storage().instance().set(&Self::k_tenant(),   &tenant);
+        e.storage().instance().set(&Self::k_landlord(), &landlord);
+        e.storage().instance().set(&Self::k_token(),    &token);
+        e.storage().instance().set(&Self::k_amount(),   &total);
+        e.storage().instance().set(&Self::k_status(),   &EscrowStatus::Init);
+        e.storage().instance().set(&Self::k_milestones(), &milestones);
+        e.storage().instance().set(&Self::k_released_total(), &0i128);
+        e.storage().instance().set(&Self::k_refunded_total(), &0i128);
+
+        Self::grant_role_internal(&e, Role::Arbitrator, arbitrator);
+        Ok(())
+    }
+
+    fn grant_role_internal(e: &Env, role: Role, addr: Address) {
+        let key = RoleKey { role: role.clone(), addr: addr.clone() };
+        e.storage().instance().set(&key, &true);
+        e.events().publish((Symbol::short("role"), Symbol::short("grant")), (role, addr));
+    }
+
+    /// Grant `role` to `addr`. Only the admin seeded at `init` may do this.
+    pub fn grant_role(e: Env, role: Role, addr: Address) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&Self::k_admin()).ok_or(Error::NotInit)?;
+        admin.require_auth();
+        Self::grant_role_internal(&e, role, addr);
+        Ok(())
+    }
+
+    /// Revoke `role` from `addr`. Only the admin seeded at `init` may do this.
+    pub fn revoke_role(e: Env, role: Role, addr: Address) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&Self::k_admin()).ok_or(Error::NotInit)?;
+        admin.require_auth();
+        let key = RoleKey { role: role.clone(), addr: addr.clone() };
+        e.storage().instance().remove(&key);
+        e.events().publish((Symbol::short("role"), Symbol::short("revoke")), (role, addr));
+        Ok(())
+    }
+
+    pub fn has_role(e: Env, role: Role, addr: Address) -> bool {
+        let key = RoleKey { role, addr };
+        e.storage().instance().get(&key).unwrap_or(false)
     }
 
     /// Tenant funds the escrow by transferring tokens to the contract address.
-    pub fn deposit(e: Env) {
-        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).unwrap();
-        if status != EscrowStatus::Init { panic!("bad state"); }
+    pub fn deposit(e: Env) -> Result<(), Error> {
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Init { return Err(Error::BadState); }
 
-        let tenant: Address   = e.storage().instance().get(&Self::k_tenant()).unwrap();
-        let token_addr: Address = e.storage().instance().get(&Self::k_token()).unwrap();
-        let amount: i128      = e.storage().instance().get(&Self::k_amount()).unwrap();
+        let tenant: Address   = e.storage().instance().get(&Self::k_tenant()).ok_or(Error::NotInit)?;
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let amount: i128      = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
 
         // auth by tenant: the token contract will enforce tenant.require_auth() internally
         let token = token::Client::new(&e, &token_addr);
@@ -51,47 +208,548 @@ impl Escrow {
         token.transfer(&tenant, &me, &amount);
 
         e.storage().instance().set(&Self::k_status(), &EscrowStatus::Funded);
+        e.events().publish((Self::topic_escrow(), Self::topic_funded()), amount);
+        Ok(())
     }
 
-    /// Arbitrator releases funds to landlord.
-    pub fn release(e: Env) {
-        let arbitrator: Address = e.storage().instance().get(&Self::k_arbit()).unwrap();
-        arbitrator.require_auth();
+    /// Any address holding the `Arbitrator` role releases funds to landlord.
+    pub fn release(e: Env, caller: Address) -> Result<(), Error> {
+        caller.require_auth();
+        if !Self::has_role(e.clone(), Role::Arbitrator, caller) { return Err(Error::Unauthorized); }
 
-        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).unwrap();
-        if status != EscrowStatus::Funded { panic!("bad state"); }
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Funded { return Err(Error::BadState); }
+        if e.storage().instance().has(&Self::k_milestones()) { return Err(Error::WrongMode); }
 
-        let landlord: Address = e.storage().instance().get(&Self::k_landlord()).unwrap();
-        let token_addr: Address = e.storage().instance().get(&Self::k_token()).unwrap();
-        let amount: i128 = e.storage().instance().get(&Self::k_amount()).unwrap();
+        let landlord: Address = e.storage().instance().get(&Self::k_landlord()).ok_or(Error::NotInit)?;
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let amount: i128 = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
 
         let token = token::Client::new(&e, &token_addr);
         let me = e.current_contract_address();
         token.transfer(&me, &landlord, &amount);
 
         e.storage().instance().set(&Self::k_status(), &EscrowStatus::Released);
+        e.events().publish((Self::topic_escrow(), Self::topic_released(), landlord), amount);
+        Ok(())
     }
 
-    /// Arbitrator refunds tenant.
-    pub fn refund(e: Env) {
-        let arbitrator: Address = e.storage().instance().get(&Self::k_arbit()).unwrap();
-        arbitrator.require_auth();
+    /// Any address holding the `Arbitrator` role refunds tenant.
+    pub fn refund(e: Env, caller: Address) -> Result<(), Error> {
+        caller.require_auth();
+        if !Self::has_role(e.clone(), Role::Arbitrator, caller) { return Err(Error::Unauthorized); }
 
-        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).unwrap();
-        if status != EscrowStatus::Funded { panic!("bad state"); }
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Funded { return Err(Error::BadState); }
+        if e.storage().instance().has(&Self::k_milestones()) { return Err(Error::WrongMode); }
 
-        let tenant: Address = e.storage().instance().get(&Self::k_tenant()).unwrap();
-        let token_addr: Address = e.storage().instance().get(&Self::k_token()).unwrap();
-        let amount: i128 = e.storage().instance().get(&Self::k_amount()).unwrap();
+        let tenant: Address = e.storage().instance().get(&Self::k_tenant()).ok_or(Error::NotInit)?;
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let amount: i128 = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
 
         let token = token::Client::new(&e, &token_addr);
         let me = e.current_contract_address();
         token.transfer(&me, &tenant, &amount);
 
         e.storage().instance().set(&Self::k_status(), &EscrowStatus::Refunded);
+        e.events().publish((Self::topic_escrow(), Self::topic_refunded(), tenant), amount);
+        Ok(())
+    }
+
+    /// Either party freezes timeout-based auto-release/refund until the
+    /// arbitrator resolves the escrow explicitly via `release`/`refund`.
+    pub fn raise_dispute(e: Env, caller: Address) -> Result<(), Error> {
+        caller.require_auth();
+        let tenant: Address = e.storage().instance().get(&Self::k_tenant()).ok_or(Error::NotInit)?;
+        let landlord: Address = e.storage().instance().get(&Self::k_landlord()).ok_or(Error::NotInit)?;
+        if caller != tenant && caller != landlord { return Err(Error::Unauthorized); }
+
+        e.storage().instance().set(&Self::k_disputed(), &true);
+        Ok(())
+    }
+
+    /// Permissionless: past the refund deadline with no dispute raised, send
+    /// the funds back to the tenant; past the release deadline, send them to
+    /// the landlord instead. Removes the dependence on a live arbitrator for
+    /// the common no-dispute case.
+    pub fn claim_timeout(e: Env) -> Result<(), Error> {
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Funded { return Err(Error::BadState); }
+        if e.storage().instance().get(&Self::k_disputed()).unwrap_or(false) { return Err(Error::Disputed); }
+
+        let now = e.ledger().timestamp();
+        let refund_deadline: Option<u64> = e.storage().instance().get(&Self::k_refund_deadline());
+        let release_deadline: Option<u64> = e.storage().instance().get(&Self::k_release_deadline());
+
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let amount: i128 = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
+        let token = token::Client::new(&e, &token_addr);
+        let me = e.current_contract_address();
+
+        if let Some(d) = refund_deadline {
+            if now >= d {
+                let tenant: Address = e.storage().instance().get(&Self::k_tenant()).ok_or(Error::NotInit)?;
+                token.transfer(&me, &tenant, &amount);
+                e.storage().instance().set(&Self::k_status(), &EscrowStatus::Refunded);
+                e.events().publish((Self::topic_escrow(), Self::topic_refunded(), tenant), amount);
+                return Ok(());
+            }
+        }
+        if let Some(d) = release_deadline {
+            if now >= d {
+                let landlord: Address = e.storage().instance().get(&Self::k_landlord()).ok_or(Error::NotInit)?;
+                token.transfer(&me, &landlord, &amount);
+                e.storage().instance().set(&Self::k_status(), &EscrowStatus::Released);
+                e.events().publish((Self::topic_escrow(), Self::topic_released(), landlord), amount);
+                return Ok(());
+            }
+        }
+        Err(Error::NotTimedOut)
+    }
+
+    /// Admin-only configuration binding this escrow to a metered unit/period
+    /// on `oracle`, an included-allowance `Reading`, and a flat per-unit price
+    /// for each resource. Required before `settle_with_utilities` can be called.
+    /// Rejected once `init_milestones` has been used: the two settlement modes
+    /// track disjoint state (`k_amount`'s single total vs. per-milestone
+    /// released/refunded amounts) and can't be mixed without leaving one of
+    /// them stale.
+    pub fn init_utilities(e: Env, oracle: Address, unit: Symbol, period: Symbol,
+                           allowance: Reading, kwh_price: i128, gas_price: i128,
+                           water_price: i128) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&Self::k_admin()).ok_or(Error::NotInit)?;
+        admin.require_auth();
+        if e.storage().instance().has(&Self::k_milestones()) { return Err(Error::WrongMode); }
+
+        e.storage().instance().set(&Self::k_util_oracle(), &oracle);
+        e.storage().instance().set(&Self::k_util_unit(), &unit);
+        e.storage().instance().set(&Self::k_util_period(), &period);
+        e.storage().instance().set(&Self::k_util_allowance(), &allowance);
+        e.storage().instance().set(&Self::k_util_prices(), &(kwh_price, gas_price, water_price));
+        Ok(())
+    }
+
+    /// Gated by the arbitrator: cross-contract-call the configured oracle for
+    /// the actual reading, pay the landlord the metered overage above the
+    /// included allowance (clamped to the funded amount), and return whatever
+    /// is left to the tenant. Terminates the escrow into `Settled`.
+    pub fn settle_with_utilities(e: Env, caller: Address) -> Result<(), Error> {
+        caller.require_auth();
+        if !Self::has_role(e.clone(), Role::Arbitrator, caller) { return Err(Error::Unauthorized); }
+
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Funded { return Err(Error::BadState); }
+        if e.storage().instance().has(&Self::k_milestones()) { return Err(Error::WrongMode); }
+
+        let oracle: Address = e.storage().instance().get(&Self::k_util_oracle()).ok_or(Error::NotConfigured)?;
+        let unit: Symbol = e.storage().instance().get(&Self::k_util_unit()).ok_or(Error::NotConfigured)?;
+        let period: Symbol = e.storage().instance().get(&Self::k_util_period()).ok_or(Error::NotConfigured)?;
+        let allowance: Reading = e.storage().instance().get(&Self::k_util_allowance()).ok_or(Error::NotConfigured)?;
+        let (kwh_price, gas_price, water_price): (i128, i128, i128) =
+            e.storage().instance().get(&Self::k_util_prices()).ok_or(Error::NotConfigured)?;
+
+        let oracle_client = UtilitiesOracleClient::new(&e, &oracle);
+        let actual = oracle_client.get_reading(&unit, &period);
+
+        let kwh_over = (actual.kwh - allowance.kwh).max(0) as i128;
+        let gas_over = (actual.gas - allowance.gas).max(0) as i128;
+        let water_over = (actual.water - allowance.water).max(0) as i128;
+        let overage = kwh_over * kwh_price + gas_over * gas_price + water_over * water_price;
+
+        let amount: i128 = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
+        let to_landlord = if overage > amount { amount } else { overage };
+        let to_tenant = amount - to_landlord;
+
+        let landlord: Address = e.storage().instance().get(&Self::k_landlord()).ok_or(Error::NotInit)?;
+        let tenant: Address = e.storage().instance().get(&Self::k_tenant()).ok_or(Error::NotInit)?;
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let token = token::Client::new(&e, &token_addr);
+        let me = e.current_contract_address();
+        if to_landlord > 0 { token.transfer(&me, &landlord, &to_landlord); }
+        if to_tenant > 0 { token.transfer(&me, &tenant, &to_tenant); }
+
+        e.storage().instance().set(&Self::k_status(), &EscrowStatus::Settled);
+        e.events().publish((Self::topic_escrow(), Self::topic_settled()), (to_landlord, to_tenant));
+        Ok(())
+    }
+
+    /// Release milestone `index`'s own amount to the landlord, leaving the
+    /// rest of the schedule untouched. Transitions the escrow as a whole to
+    /// `Settled` once every milestone has been either released or refunded.
+    pub fn release_milestone(e: Env, caller: Address, index: u32) -> Result<(), Error> {
+        caller.require_auth();
+        if !Self::has_role(e.clone(), Role::Arbitrator, caller) { return Err(Error::Unauthorized); }
+
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Funded { return Err(Error::BadState); }
+
+        let mut milestones: Vec<Milestone> = e.storage().instance().get(&Self::k_milestones()).ok_or(Error::NotInit)?;
+        let mut m = milestones.get(index).ok_or(Error::MilestoneNotFound)?;
+        if m.released || m.refunded { return Err(Error::AlreadyResolved); }
+
+        let total: i128 = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
+        let released_total: i128 = e.storage().instance().get(&Self::k_released_total()).ok_or(Error::NotInit)?;
+        let refunded_total: i128 = e.storage().instance().get(&Self::k_refunded_total()).ok_or(Error::NotInit)?;
+        if released_total + refunded_total + m.amount > total { return Err(Error::OverRelease); }
+
+        let landlord: Address = e.storage().instance().get(&Self::k_landlord()).ok_or(Error::NotInit)?;
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let token = token::Client::new(&e, &token_addr);
+        let me = e.current_contract_address();
+        token.transfer(&me, &landlord, &m.amount);
+
+        let milestone_amount = m.amount;
+        let released_total = released_total + milestone_amount;
+        m.released = true;
+        milestones.set(index, m);
+        e.storage().instance().set(&Self::k_milestones(), &milestones);
+        e.storage().instance().set(&Self::k_released_total(), &released_total);
+        e.events().publish((Self::topic_escrow(), Self::topic_released(), Self::topic_milestone()), (index, landlord, milestone_amount));
+
+        if released_total + refunded_total >= total {
+            e.storage().instance().set(&Self::k_status(), &EscrowStatus::Settled);
+        }
+        Ok(())
+    }
+
+    /// Refund milestone `index`'s own amount to the tenant. See `release_milestone`.
+    pub fn refund_milestone(e: Env, caller: Address, index: u32) -> Result<(), Error> {
+        caller.require_auth();
+        if !Self::has_role(e.clone(), Role::Arbitrator, caller) { return Err(Error::Unauthorized); }
+
+        let status: EscrowStatus = e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)?;
+        if status != EscrowStatus::Funded { return Err(Error::BadState); }
+
+        let mut milestones: Vec<Milestone> = e.storage().instance().get(&Self::k_milestones()).ok_or(Error::NotInit)?;
+        let mut m = milestones.get(index).ok_or(Error::MilestoneNotFound)?;
+        if m.released || m.refunded { return Err(Error::AlreadyResolved); }
+
+        let total: i128 = e.storage().instance().get(&Self::k_amount()).ok_or(Error::NotInit)?;
+        let released_total: i128 = e.storage().instance().get(&Self::k_released_total()).ok_or(Error::NotInit)?;
+        let refunded_total: i128 = e.storage().instance().get(&Self::k_refunded_total()).ok_or(Error::NotInit)?;
+        if released_total + refunded_total + m.amount > total { return Err(Error::OverRelease); }
+
+        let tenant: Address = e.storage().instance().get(&Self::k_tenant()).ok_or(Error::NotInit)?;
+        let token_addr: Address = e.storage().instance().get(&Self::k_token()).ok_or(Error::NotInit)?;
+        let token = token::Client::new(&e, &token_addr);
+        let me = e.current_contract_address();
+        token.transfer(&me, &tenant, &m.amount);
+
+        let milestone_amount = m.amount;
+        let refunded_total = refunded_total + milestone_amount;
+        m.refunded = true;
+        milestones.set(index, m);
+        e.storage().instance().set(&Self::k_milestones(), &milestones);
+        e.storage().instance().set(&Self::k_refunded_total(), &refunded_total);
+        e.events().publish((Self::topic_escrow(), Self::topic_refunded(), Self::topic_milestone()), (index, tenant, milestone_amount));
+
+        if released_total + refunded_total >= total {
+            e.storage().instance().set(&Self::k_status(), &EscrowStatus::Settled);
+        }
+        Ok(())
+    }
+
+    pub fn milestones(e: Env) -> Result<Vec<Milestone>, Error> {
+        e.storage().instance().get(&Self::k_milestones()).ok_or(Error::NotInit)
+    }
+
+    pub fn released_total(e: Env) -> Result<i128, Error> {
+        e.storage().instance().get(&Self::k_released_total()).ok_or(Error::NotInit)
+    }
+
+    pub fn refunded_total(e: Env) -> Result<i128, Error> {
+        e.storage().instance().get(&Self::k_refunded_total()).ok_or(Error::NotInit)
+    }
+
+    pub fn status(e: Env) -> Result<EscrowStatus, Error> {
+        e.storage().instance().get(&Self::k_status()).ok_or(Error::NotInit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use soroban_sdk::testutils::Address as _;
+    use utilities_oracle::UtilitiesOracle;
+
+    fn setup(e: &Env) -> (Address, Address, Address, Address, Address, i128) {
+        let admin = Address::generate(e);
+        let tenant = Address::generate(e);
+        let landlord = Address::generate(e);
+        let arbitrator = Address::generate(e);
+        let token_admin = Address::generate(e);
+        let token = e.register_stellar_asset_contract_v2(token_admin).address();
+        (admin, tenant, landlord, arbitrator, token, 1_000)
+    }
+
+    #[test]
+    fn test_deposit_then_release_pays_landlord() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+
+        client.deposit();
+        assert!(client.status() == EscrowStatus::Funded);
+
+        client.release(&arbitrator);
+        assert!(client.status() == EscrowStatus::Released);
+        assert_eq!(token::Client::new(&e, &token).balance(&landlord), amount);
+    }
+
+    #[test]
+    fn test_refund_returns_deposit_to_tenant() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+        client.deposit();
+
+        client.refund(&arbitrator);
+        assert!(client.status() == EscrowStatus::Refunded);
+        assert_eq!(token::Client::new(&e, &token).balance(&tenant), amount);
+    }
+
+    #[test]
+    fn test_release_without_arbitrator_role_returns_unauthorized() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+        client.deposit();
+
+        let stranger = Address::generate(&e);
+        let result = client.try_release(&stranger);
+        assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    }
+
+    #[test]
+    fn test_double_init_returns_already_init_error() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+
+        let result = client.try_init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+        assert_eq!(result, Err(Ok(Error::AlreadyInit)));
     }
 
-    pub fn status(e: Env) -> EscrowStatus {
-        e.storage().instance().get(&Self::k_status()).unwrap()
+    #[test]
+    fn test_claim_timeout_refunds_tenant_past_refund_deadline() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &Some(1_000));
+        client.deposit();
+
+        e.ledger().set_timestamp(1_000);
+        client.claim_timeout();
+        assert!(client.status() == EscrowStatus::Refunded);
+        assert_eq!(token::Client::new(&e, &token).balance(&tenant), amount);
+    }
+
+    #[test]
+    fn test_claim_timeout_releases_to_landlord_past_release_deadline() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &Some(1_000), &None);
+        client.deposit();
+
+        e.ledger().set_timestamp(1_000);
+        client.claim_timeout();
+        assert!(client.status() == EscrowStatus::Released);
+        assert_eq!(token::Client::new(&e, &token).balance(&landlord), amount);
+    }
+
+    #[test]
+    fn test_raise_dispute_blocks_claim_timeout() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &Some(1_000));
+        client.deposit();
+        client.raise_dispute(&tenant);
+
+        e.ledger().set_timestamp(1_000);
+        let result = client.try_claim_timeout();
+        assert_eq!(result, Err(Ok(Error::Disputed)));
+    }
+
+    #[test]
+    fn test_milestones_release_and_refund_settle_once_all_resolved() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, _) = setup(&e);
+        let total = 300;
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &total);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        let amounts = Vec::from_array(&e, [100i128, 200i128]);
+        client.init_milestones(&admin, &tenant, &landlord, &arbitrator, &token, &amounts);
+        client.deposit();
+
+        client.release_milestone(&arbitrator, &0);
+        assert_eq!(client.released_total(), 100);
+        assert!(client.status() == EscrowStatus::Funded);
+
+        client.refund_milestone(&arbitrator, &1);
+        assert_eq!(client.refunded_total(), 200);
+        assert!(client.status() == EscrowStatus::Settled);
+
+        assert_eq!(token::Client::new(&e, &token).balance(&landlord), 100);
+        assert_eq!(token::Client::new(&e, &token).balance(&tenant), 200);
+    }
+
+    #[test]
+    fn test_milestone_double_resolve_returns_already_resolved() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, _) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &300);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        let amounts = Vec::from_array(&e, [100i128, 200i128]);
+        client.init_milestones(&admin, &tenant, &landlord, &arbitrator, &token, &amounts);
+        client.deposit();
+
+        client.release_milestone(&arbitrator, &0);
+        let result = client.try_release_milestone(&arbitrator, &0);
+        assert_eq!(result, Err(Ok(Error::AlreadyResolved)));
+    }
+
+    #[test]
+    fn test_settle_with_utilities_bills_overage_and_returns_remainder() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let oracle_admin = Address::generate(&e);
+        let oracle_id = e.register_contract(None, UtilitiesOracle);
+        let oracle_client = utilities_oracle::UtilitiesOracleClient::new(&e, &oracle_id);
+        oracle_client.init(&oracle_admin);
+
+        let unit = Symbol::short("unit1");
+        let period = Symbol::short("2026_01");
+        // 50 kWh over the included allowance at a price of 2 per unit = 100 overage.
+        oracle_client.set_reading(&oracle_admin, &unit, &period, &150, &0, &0);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+        client.deposit();
+
+        let allowance = Reading { kwh: 100, gas: 0, water: 0 };
+        client.init_utilities(&oracle_id, &unit, &period, &allowance, &2, &0, &0);
+
+        client.settle_with_utilities(&arbitrator);
+        assert!(client.status() == EscrowStatus::Settled);
+        assert_eq!(token::Client::new(&e, &token).balance(&landlord), 100);
+        assert_eq!(token::Client::new(&e, &token).balance(&tenant), amount - 100);
+    }
+
+    #[test]
+    fn test_init_utilities_rejects_milestone_escrow() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, _) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &300);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        let amounts = Vec::from_array(&e, [100i128, 200i128]);
+        client.init_milestones(&admin, &tenant, &landlord, &arbitrator, &token, &amounts);
+
+        let oracle_id = e.register_contract(None, UtilitiesOracle);
+        let allowance = Reading { kwh: 100, gas: 0, water: 0 };
+        let result = client.try_init_utilities(
+            &oracle_id, &Symbol::short("unit1"), &Symbol::short("2026_01"), &allowance, &2, &0, &0,
+        );
+        assert_eq!(result, Err(Ok(Error::WrongMode)));
+    }
+
+    #[test]
+    fn test_release_rejects_milestone_escrow() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, _) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &300);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        let amounts = Vec::from_array(&e, [100i128, 200i128]);
+        client.init_milestones(&admin, &tenant, &landlord, &arbitrator, &token, &amounts);
+        client.deposit();
+
+        let result = client.try_release(&arbitrator);
+        assert_eq!(result, Err(Ok(Error::WrongMode)));
+    }
+
+    #[test]
+    fn test_refund_rejects_milestone_escrow() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, _) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &300);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        let amounts = Vec::from_array(&e, [100i128, 200i128]);
+        client.init_milestones(&admin, &tenant, &landlord, &arbitrator, &token, &amounts);
+        client.deposit();
+
+        let result = client.try_refund(&arbitrator);
+        assert_eq!(result, Err(Ok(Error::WrongMode)));
+    }
+
+    #[test]
+    fn test_grant_and_revoke_role() {
+        let e = Env::default();
+        e.mock_all_auths();
+        let (admin, tenant, landlord, arbitrator, token, amount) = setup(&e);
+        token::StellarAssetClient::new(&e, &token).mint(&tenant, &amount);
+
+        let contract_id = e.register_contract(None, Escrow);
+        let client = EscrowClient::new(&e, &contract_id);
+        client.init(&admin, &tenant, &landlord, &arbitrator, &token, &amount, &None, &None);
+        client.deposit();
+
+        let second_arbitrator = Address::generate(&e);
+        assert!(!client.has_role(&Role::Arbitrator, &second_arbitrator));
+
+        client.grant_role(&Role::Arbitrator, &second_arbitrator);
+        assert!(client.has_role(&Role::Arbitrator, &second_arbitrator));
+        client.release(&second_arbitrator);
+        assert!(client.status() == EscrowStatus::Released);
     }
 }
@@ -1,31 +1,159 @@
 #![no_std]
-use soroban_sdk::{Env, Map, Symbol, Vec};
-use crate::types::Node;
+use soroban_sdk::{contracttype, Address, BytesN, Env, Map, Symbol, Vec};
+use crate::types::{Node, SubleaseToken};
 
-pub fn k_next() -> Symbol { Symbol::short("next") }
-pub fn k_leases() -> Symbol { Symbol::short("lease") }
-pub fn k_kids() -> Symbol { Symbol::short("kids") }
+/// Persistent-storage keys. Each lease, each child list, each history log and
+/// each event-ring slot lives under its own entry (`Lease(id)` / `Kids(id)` /
+/// `History(id)` / `EventSlot(seq % EVENT_RING_CAP)`) so a write to one never
+/// touches the serialized bytes of any other — unlike the single
+/// instance-storage `Map` these used to be, which was rewritten in full on
+/// every mutation.
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Next,
+    Lease(u64),
+    Kids(u64),
+    History(u64),
+    EventSlot(u64),
+}
+
+pub fn k_deposits() -> Symbol { Symbol::short("deposit") }
+pub fn k_sub_tokens() -> Symbol { Symbol::short("subtok") }
+pub fn k_event_seq() -> Symbol { Symbol::short("evseq") }
+pub fn k_status_epoch() -> Symbol { Symbol::short("stepoch") }
 
 pub fn next_id(e: &Env) -> u64 {
-    let k = k_next();
-    let mut n: u64 = e.storage().instance().get(&k).unwrap_or(0);
+    let mut n: u64 = e.storage().instance().get(&DataKey::Next).unwrap_or(0);
     n += 1;
-    e.storage().instance().set(&k, &n);
+    e.storage().instance().set(&DataKey::Next, &n);
     n
 }
 
-pub fn get_leases(e: &Env) -> Map<u64, Node> {
-    e.storage().instance().get(&k_leases()).unwrap_or(Map::new(e))
+// Ledger close time is approximately 5s on both testnet and mainnet; used only
+// to translate a lease's unix-second `expiry_ts` into a ledger-count TTL bound.
+const APPROX_SECS_PER_LEDGER: u64 = 5;
+// Generous cap so a single `extend_ttl` call can't be asked to push an entry's
+// TTL further out than the network allows.
+const MAX_PERSISTENT_TTL_LEDGERS: u32 = 3_110_400; // ~6 months at 5s/ledger
+
+fn ttl_bound_for_expiry(e: &Env, expiry_ts: u64) -> u32 {
+    let now = e.ledger().timestamp();
+    let remaining_secs = expiry_ts.saturating_sub(now);
+    let remaining_ledgers = remaining_secs / APPROX_SECS_PER_LEDGER;
+    if remaining_ledgers > MAX_PERSISTENT_TTL_LEDGERS as u64 {
+        MAX_PERSISTENT_TTL_LEDGERS
+    } else {
+        remaining_ledgers as u32
+    }
+}
+
+pub fn load_lease(e: &Env, id: u64) -> Option<Node> {
+    e.storage().persistent().get(&DataKey::Lease(id))
+}
+
+/// Write a lease entry and bump its TTL up to a bound derived from its own
+/// `expiry_ts`, so an active lease stays live while an expired one is free to
+/// lapse off persistent storage instead of being archived forever.
+pub fn save_lease(e: &Env, id: u64, node: &Node) {
+    let key = DataKey::Lease(id);
+    e.storage().persistent().set(&key, node);
+    let ttl = ttl_bound_for_expiry(e, node.expiry_ts);
+    if ttl > 0 {
+        e.storage().persistent().extend_ttl(&key, ttl / 2, ttl);
+    }
+}
+
+pub fn remove_lease(e: &Env, id: u64) {
+    e.storage().persistent().remove(&DataKey::Lease(id));
 }
 
-pub fn put_leases(e: &Env, m: &Map<u64, Node>) {
-    e.storage().instance().set(&k_leases(), m);
+pub fn load_kids(e: &Env, id: u64) -> Vec<u64> {
+    e.storage().persistent().get(&DataKey::Kids(id)).unwrap_or(Vec::new(e))
 }
 
-pub fn get_kids(e: &Env) -> Map<u64, Vec<u64>> {
-    e.storage().instance().get(&k_kids()).unwrap_or(Map::new(e))
+pub fn save_kids(e: &Env, id: u64, v: &Vec<u64>) {
+    let key = DataKey::Kids(id);
+    e.storage().persistent().set(&key, v);
+    e.storage().persistent().extend_ttl(
+        &key,
+        MAX_PERSISTENT_TTL_LEDGERS / 2,
+        MAX_PERSISTENT_TTL_LEDGERS,
+    );
+}
+
+// Deposits and capability tokens remain single maps in instance storage, each
+// rewritten in full on every write: one entry per node holding a deposit and
+// one per registered token, outside the per-lease entries above.
+pub fn get_deposits(e: &Env) -> Map<u64, (Address, i128)> {
+    e.storage().instance().get(&k_deposits()).unwrap_or(Map::new(e))
+}
+
+pub fn put_deposits(e: &Env, m: &Map<u64, (Address, i128)>) {
+    e.storage().instance().set(&k_deposits(), m);
+}
+
+pub fn get_sub_tokens(e: &Env) -> Map<(u64, BytesN<32>), SubleaseToken> {
+    e.storage().instance().get(&k_sub_tokens()).unwrap_or(Map::new(e))
+}
+
+pub fn put_sub_tokens(e: &Env, m: &Map<(u64, BytesN<32>), SubleaseToken>) {
+    e.storage().instance().set(&k_sub_tokens(), m);
+}
+
+pub fn load_history(e: &Env, id: u64) -> Vec<(u32, u64, Address)> {
+    e.storage().persistent().get(&DataKey::History(id)).unwrap_or(Vec::new(e))
+}
+
+pub fn save_history(e: &Env, id: u64, log: &Vec<(u32, u64, Address)>) {
+    let key = DataKey::History(id);
+    e.storage().persistent().set(&key, log);
+    e.storage().persistent().extend_ttl(
+        &key,
+        MAX_PERSISTENT_TTL_LEDGERS / 2,
+        MAX_PERSISTENT_TTL_LEDGERS,
+    );
+}
+
+/// Monotonically increasing counter behind the global event ring buffer; never
+/// reused even as old slots get overwritten, so callers can detect gaps.
+pub fn next_event_seq(e: &Env) -> u64 {
+    let mut n: u64 = e.storage().instance().get(&k_event_seq()).unwrap_or(0);
+    n += 1;
+    e.storage().instance().set(&k_event_seq(), &n);
+    n
+}
+
+pub fn latest_event_seq(e: &Env) -> u64 {
+    e.storage().instance().get(&k_event_seq()).unwrap_or(0)
+}
+
+/// Load one event-ring slot, keyed by its own persistent entry so recording a
+/// new event never touches the serialized bytes of any other slot.
+pub fn load_event_slot(e: &Env, slot: u64) -> Option<(u64, Symbol, u64)> {
+    e.storage().persistent().get(&DataKey::EventSlot(slot))
+}
+
+pub fn save_event_slot(e: &Env, slot: u64, entry: &(u64, Symbol, u64)) {
+    let key = DataKey::EventSlot(slot);
+    e.storage().persistent().set(&key, entry);
+    e.storage().persistent().extend_ttl(
+        &key,
+        MAX_PERSISTENT_TTL_LEDGERS / 2,
+        MAX_PERSISTENT_TTL_LEDGERS,
+    );
+}
+
+/// Global change-detection counter: bumped once per lifecycle transition so an
+/// off-chain watcher can poll this single cheap value instead of re-reading
+/// the whole tree to notice something changed.
+pub fn bump_status_epoch(e: &Env) -> u64 {
+    let mut n: u64 = e.storage().instance().get(&k_status_epoch()).unwrap_or(0);
+    n += 1;
+    e.storage().instance().set(&k_status_epoch(), &n);
+    n
 }
 
-pub fn put_kids(e: &Env, m: &Map<u64, Vec<u64>>) {
-    e.storage().instance().set(&k_kids(), m);
+pub fn get_status_epoch(e: &Env) -> u64 {
+    e.storage().instance().get(&k_status_epoch()).unwrap_or(0)
 }
@@ -16,3 +16,39 @@ pub struct Node {
     pub accepted: bool,
     pub active: bool,
 }
+
+/// A capability token registered by a lessee, delegating the right to create
+/// up to `max_subleases` subleases under a node to `permitted` without
+/// sharing the lessee's own signing key.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubleaseToken {
+    pub permitted: Address,
+    pub remaining: u32,
+}
+
+/// Derived lifecycle state of a node, computed on read from `accepted`/`active`
+/// and the ledger clock rather than stored directly. Expiry always overrides
+/// `active`: a node whose `expiry_ts` has passed reports `Expired` even if it
+/// was never explicitly deactivated.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Status {
+    Draft,
+    Accepted,
+    Active,
+    Delinquent,
+    Expired,
+    Canceled,
+}
+
+/// Chain-wide activation eligibility for a single node, as opposed to `Status`'s
+/// richer per-node lifecycle: a node is only `Active` when its own `active` flag
+/// is set AND every ancestor up to the root is also active, mirroring a
+/// power-broker's "Satisfied only when all dependencies are met" rule.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EffectiveStatus {
+    Active,
+    Pending,
+}
@@ -1,8 +1,22 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, Address, Env, Symbol
+    contract, contracterror, contractimpl, contracttype, Address, Env, Symbol
 };
 
+/// Structured failure codes, returned instead of panicking, so a caller can
+/// distinguish a wrong-state oracle from an unauthorized or invalid write
+/// without parsing a trap message.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    AlreadyInit = 1,
+    NotInit = 2,
+    Unauthorized = 3,
+    NoReading = 4,
+    NegativeValue = 5,
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Reading {
@@ -11,42 +25,144 @@ pub struct Reading {
     pub water: i64,
 }
 
+/// Composite persistent-storage key for a single meter/billing-period reading,
+/// so every `(unit, period)` pair gets its own entry instead of all writes
+/// clobbering a single instance-storage slot.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReadingKey {
+    pub unit: Symbol,
+    pub period: Symbol,
+}
+
+/// Roles an address can hold against this oracle, checked via `has_role`
+/// rather than a single hardcoded admin address, so metering feeds can be
+/// rotated or multiplied without redeploying.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Role {
+    Admin,
+    OracleWriter,
+}
+
+/// Composite storage key for a single `(role, address)` grant, mirroring
+/// `ReadingKey`'s keying pattern.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleKey {
+    pub role: Role,
+    pub addr: Address,
+}
+
 fn k_admin() -> Symbol { Symbol::short("admin") }
-fn k_reading() -> Symbol { Symbol::short("reading") }
+fn k_ttl() -> Symbol { Symbol::short("ttl") }
+
+// Central topic symbols so indexers have one place to learn what to subscribe to.
+fn topic_reading_set() -> Symbol { Symbol::short("readset") }
+
+// Used whenever the admin hasn't called `set_default_ttl`.
+const DEFAULT_TTL_THRESHOLD: u32 = 100;
+const DEFAULT_TTL_EXTEND_TO: u32 = 518_400; // ~30 days at 5s/ledger
+
+fn default_ttl(e: &Env) -> (u32, u32) {
+    e.storage().instance().get(&k_ttl()).unwrap_or((DEFAULT_TTL_THRESHOLD, DEFAULT_TTL_EXTEND_TO))
+}
 
 #[contract]
 pub struct UtilitiesOracle;
 
 #[contractimpl]
 impl UtilitiesOracle {
-    pub fn init(e: Env, admin: Address) {
+    /// One-time initializer. Seeds `admin` (who alone can grant/revoke roles)
+    /// and grants them the `OracleWriter` role so existing callers don't need
+    /// a separate `grant_role` call to get started.
+    pub fn init(e: Env, admin: Address) -> Result<(), Error> {
         // one-time init
-        if e.storage().instance().has(&k_admin()) { panic!("inited"); }
+        if e.storage().instance().has(&k_admin()) { return Err(Error::AlreadyInit); }
         e.storage().instance().set(&k_admin(), &admin);
+        Self::grant_role_internal(&e, Role::OracleWriter, admin);
+        Ok(())
+    }
+
+    fn grant_role_internal(e: &Env, role: Role, addr: Address) {
+        let key = RoleKey { role: role.clone(), addr: addr.clone() };
+        e.storage().instance().set(&key, &true);
+        e.events().publish((Symbol::short("role"), Symbol::short("grant")), (role, addr));
+    }
+
+    /// Grant `role` to `addr`. Only the admin seeded at `init` may do this.
+    pub fn grant_role(e: Env, role: Role, addr: Address) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&k_admin()).ok_or(Error::NotInit)?;
+        admin.require_auth();
+        Self::grant_role_internal(&e, role, addr);
+        Ok(())
     }
 
-    pub fn set_reading(e: Env, unit: Symbol, period: Symbol, kwh: i64, gas: i64, water: i64) {
-        // only admin can write (mock "oracle")
-        let admin: Address = e.storage().instance().get(&k_admin()).expect("no-admin");
+    /// Revoke `role` from `addr`. Only the admin seeded at `init` may do this.
+    pub fn revoke_role(e: Env, role: Role, addr: Address) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&k_admin()).ok_or(Error::NotInit)?;
         admin.require_auth();
+        let key = RoleKey { role: role.clone(), addr: addr.clone() };
+        e.storage().instance().remove(&key);
+        e.events().publish((Symbol::short("role"), Symbol::short("revoke")), (role, addr));
+        Ok(())
+    }
+
+    pub fn has_role(e: Env, role: Role, addr: Address) -> bool {
+        let key = RoleKey { role, addr };
+        e.storage().instance().get(&key).unwrap_or(false)
+    }
+
+    /// Change the TTL threshold/extend-to applied to every reading written
+    /// after this call. Existing entries keep whatever TTL they were last
+    /// bumped to until they're written or `bump`ed again.
+    pub fn set_default_ttl(e: Env, threshold: u32, extend_to: u32) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&k_admin()).ok_or(Error::NotInit)?;
+        admin.require_auth();
+        e.storage().instance().set(&k_ttl(), &(threshold, extend_to));
+        Ok(())
+    }
+
+    pub fn set_reading(e: Env, caller: Address, unit: Symbol, period: Symbol, kwh: i64, gas: i64, water: i64) -> Result<(), Error> {
+        caller.require_auth();
+        if !Self::has_role(e.clone(), Role::OracleWriter, caller) { return Err(Error::Unauthorized); }
 
         // simple bounds (optional)
-        if kwh < 0 || gas < 0 || water < 0 { panic!("neg"); }
+        if kwh < 0 || gas < 0 || water < 0 { return Err(Error::NegativeValue); }
 
-        // store using a simple key (for demo purposes)
+        let key = ReadingKey { unit: unit.clone(), period: period.clone() };
         let reading = Reading { kwh, gas, water };
-        e.storage().instance().set(&k_reading(), &reading);
+        e.storage().persistent().set(&key, &reading);
+        let (threshold, extend_to) = default_ttl(&e);
+        e.storage().persistent().extend_ttl(&key, threshold, extend_to);
+        e.events().publish((topic_reading_set(), unit, period), reading);
+        Ok(())
+    }
+
+    pub fn get_reading(e: Env, unit: Symbol, period: Symbol) -> Result<Reading, Error> {
+        let key = ReadingKey { unit, period };
+        e.storage().persistent().get(&key).ok_or(Error::NoReading)
     }
 
-    pub fn get_reading(e: Env, unit: Symbol, period: Symbol) -> Reading {
-        e.storage().instance().get(&k_reading()).expect("no-reading")
+    /// Admin-only: extend a reading's TTL without rewriting it, so a
+    /// historical reading can be kept around past its default lifetime (e.g.
+    /// for an ongoing dispute) without being resubmitted.
+    pub fn bump(e: Env, unit: Symbol, period: Symbol, extend_to: u32) -> Result<(), Error> {
+        let admin: Address = e.storage().instance().get(&k_admin()).ok_or(Error::NotInit)?;
+        admin.require_auth();
+
+        let key = ReadingKey { unit, period };
+        if !e.storage().persistent().has(&key) { return Err(Error::NoReading); }
+        let (threshold, _) = default_ttl(&e);
+        e.storage().persistent().extend_ttl(&key, threshold, extend_to);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use soroban_sdk::{testutils::Address as _, Env};
+    use soroban_sdk::{testutils::Address as _, Env, IntoVal};
 
     #[test]
     fn test_init_and_set_reading() {
@@ -65,7 +181,7 @@ mod test {
         client.init(&admin);
 
         // Set a reading
-        client.set_reading(&unit, &period, &320, &14, &6800);
+        client.set_reading(&admin, &unit, &period, &320, &14, &6800);
 
         // Get the reading back
         let reading = client.get_reading(&unit, &period);
@@ -75,8 +191,7 @@ mod test {
     }
 
     #[test]
-    #[should_panic(expected = "inited")]
-    fn test_double_init_panics() {
+    fn test_double_init_returns_already_init_error() {
         let e = Env::default();
         let admin = Address::generate(&e);
 
@@ -88,14 +203,15 @@ mod test {
         // First init should succeed
         client.init(&admin);
 
-        // Second init should panic
-        client.init(&admin);
+        // Second init should come back as a structured error, not a trap.
+        let result = client.try_init(&admin);
+        assert_eq!(result, Err(Ok(Error::AlreadyInit)));
     }
 
     #[test]
-    #[should_panic(expected = "no-admin")]
-    fn test_set_reading_without_init_panics() {
+    fn test_set_reading_without_role_returns_unauthorized() {
         let e = Env::default();
+        let caller = Address::generate(&e);
         let unit = Symbol::short("unit:NYC:123-A");
         let period = Symbol::short("2025-10");
 
@@ -104,12 +220,12 @@ mod test {
         let contract_id = e.register_contract(None, UtilitiesOracle);
         let client = UtilitiesOracleClient::new(&e, &contract_id);
 
-        // Try to set reading without initializing admin
-        client.set_reading(&unit, &period, &320, &14, &6800);
+        // No role was ever granted, so the caller is rejected as unauthorized.
+        let result = client.try_set_reading(&caller, &unit, &period, &320, &14, &6800);
+        assert_eq!(result, Err(Ok(Error::Unauthorized)));
     }
 
     #[test]
-    #[should_panic(expected = "neg")]
     fn test_negative_values_rejected() {
         let e = Env::default();
         let admin = Address::generate(&e);
@@ -124,12 +240,12 @@ mod test {
         client.init(&admin);
 
         // Try to set negative values
-        client.set_reading(&unit, &period, &-1, &14, &6800);
+        let result = client.try_set_reading(&admin, &unit, &period, &-1, &14, &6800);
+        assert_eq!(result, Err(Ok(Error::NegativeValue)));
     }
 
     #[test]
-    #[should_panic(expected = "no-reading")]
-    fn test_get_nonexistent_reading_panics() {
+    fn test_get_nonexistent_reading_returns_no_reading() {
         let e = Env::default();
         let admin = Address::generate(&e);
         let unit = Symbol::short("unit:NYC:123-A");
@@ -143,7 +259,8 @@ mod test {
         client.init(&admin);
 
         // Try to get reading that doesn't exist
-        client.get_reading(&unit, &period);
+        let result = client.try_get_reading(&unit, &period);
+        assert_eq!(result, Err(Ok(Error::NoReading)));
     }
 
     #[test]
@@ -163,9 +280,9 @@ mod test {
         client.init(&admin);
 
         // Set multiple readings
-        client.set_reading(&unit1, &period1, &320, &14, &6800);
-        client.set_reading(&unit1, &period2, &350, &16, &7200);
-        client.set_reading(&unit2, &period1, &280, &12, &6500);
+        client.set_reading(&admin, &unit1, &period1, &320, &14, &6800);
+        client.set_reading(&admin, &unit1, &period2, &350, &16, &7200);
+        client.set_reading(&admin, &unit2, &period1, &280, &12, &6500);
 
         // Verify all readings
         let reading1 = client.get_reading(&unit1, &period1);
@@ -199,7 +316,7 @@ mod test {
         client.init(&admin);
 
         // Set reading with zero values
-        client.set_reading(&unit, &period, &0, &0, &0);
+        client.set_reading(&admin, &unit, &period, &0, &0, &0);
 
         let reading = client.get_reading(&unit, &period);
         assert_eq!(reading.kwh, 0);
@@ -207,6 +324,114 @@ mod test {
         assert_eq!(reading.water, 0);
     }
 
+    #[test]
+    fn test_bump_extends_ttl_without_admin_resubmitting_reading() {
+        let e = Env::default();
+        let admin = Address::generate(&e);
+        let unit = Symbol::short("unit:NYC:123-A");
+        let period = Symbol::short("2025-10");
+
+        e.mock_all_auths();
+
+        let contract_id = e.register_contract(None, UtilitiesOracle);
+        let client = UtilitiesOracleClient::new(&e, &contract_id);
+
+        client.init(&admin);
+        client.set_reading(&admin, &unit, &period, &320, &14, &6800);
+
+        client.bump(&unit, &period, &1_000_000);
+        let reading = client.get_reading(&unit, &period);
+        assert_eq!(reading.kwh, 320);
+    }
+
+    #[test]
+    fn test_bump_rejects_unknown_reading() {
+        let e = Env::default();
+        let admin = Address::generate(&e);
+        let unit = Symbol::short("unit:NYC:123-A");
+        let period = Symbol::short("2025-10");
+
+        e.mock_all_auths();
+
+        let contract_id = e.register_contract(None, UtilitiesOracle);
+        let client = UtilitiesOracleClient::new(&e, &contract_id);
+
+        client.init(&admin);
+        let result = client.try_bump(&unit, &period, &1_000_000);
+        assert_eq!(result, Err(Ok(Error::NoReading)));
+    }
+
+    #[test]
+    fn test_set_default_ttl_applies_to_subsequent_writes() {
+        let e = Env::default();
+        let admin = Address::generate(&e);
+        let unit = Symbol::short("unit:NYC:123-A");
+        let period = Symbol::short("2025-10");
+
+        e.mock_all_auths();
+
+        let contract_id = e.register_contract(None, UtilitiesOracle);
+        let client = UtilitiesOracleClient::new(&e, &contract_id);
+
+        client.init(&admin);
+        client.set_default_ttl(&50, &200_000);
+        client.set_reading(&admin, &unit, &period, &320, &14, &6800);
+
+        let reading = client.get_reading(&unit, &period);
+        assert_eq!(reading.kwh, 320);
+    }
+
+    #[test]
+    fn test_oracle_writer_role_can_be_rotated() {
+        let e = Env::default();
+        let admin = Address::generate(&e);
+        let feed1 = Address::generate(&e);
+        let feed2 = Address::generate(&e);
+        let unit = Symbol::short("unit:NYC:123-A");
+        let period = Symbol::short("2025-10");
+
+        e.mock_all_auths();
+
+        let contract_id = e.register_contract(None, UtilitiesOracle);
+        let client = UtilitiesOracleClient::new(&e, &contract_id);
+
+        client.init(&admin);
+        assert!(!client.has_role(&Role::OracleWriter, &feed1));
+
+        client.grant_role(&Role::OracleWriter, &feed1);
+        assert!(client.has_role(&Role::OracleWriter, &feed1));
+        client.set_reading(&feed1, &unit, &period, &320, &14, &6800);
+
+        client.revoke_role(&Role::OracleWriter, &feed1);
+        client.grant_role(&Role::OracleWriter, &feed2);
+        assert!(!client.has_role(&Role::OracleWriter, &feed1));
+        client.set_reading(&feed2, &unit, &period, &350, &16, &7200);
+
+        let reading = client.get_reading(&unit, &period);
+        assert_eq!(reading.kwh, 350);
+    }
+
+    #[test]
+    fn test_set_reading_rejects_revoked_writer() {
+        let e = Env::default();
+        let admin = Address::generate(&e);
+        let feed = Address::generate(&e);
+        let unit = Symbol::short("unit:NYC:123-A");
+        let period = Symbol::short("2025-10");
+
+        e.mock_all_auths();
+
+        let contract_id = e.register_contract(None, UtilitiesOracle);
+        let client = UtilitiesOracleClient::new(&e, &contract_id);
+
+        client.init(&admin);
+        client.grant_role(&Role::OracleWriter, &feed);
+        client.revoke_role(&Role::OracleWriter, &feed);
+
+        let result = client.try_set_reading(&feed, &unit, &period, &320, &14, &6800);
+        assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    }
+
     #[test]
     fn test_event_emission() {
         let e = Env::default();
@@ -222,17 +447,22 @@ mod test {
         client.init(&admin);
 
         // Set reading and check events
-        client.set_reading(&unit, &period, &320, &14, &6800);
+        client.set_reading(&admin, &unit, &period, &320, &14, &6800);
 
-        // Check that event was emitted
+        // `init` itself publishes a role-grant event, so `reading_set` is the
+        // second and most recent one.
         let events = e.events().all();
-        assert_eq!(events.len(), 1);
-        
-        let event = &events[0];
-        assert_eq!(event.event.type_, soroban_sdk::xdr::ContractEventType::Contract);
-        
-        // Verify event data structure
-        let event_data = &event.event.body.contract_event;
-        assert_eq!(event_data.contract_id, contract_id);
+        assert_eq!(events.len(), 2);
+
+        let (event_contract_id, topics, data) = events.last().unwrap();
+        assert_eq!(event_contract_id, &contract_id);
+        assert_eq!(
+            topics,
+            &(topic_reading_set(), unit.clone(), period.clone()).into_val(&e)
+        );
+        assert_eq!(
+            data,
+            &Reading { kwh: 320, gas: 14, water: 6800 }.into_val(&e)
+        );
     }
 }
\ No newline at end of file